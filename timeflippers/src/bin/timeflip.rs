@@ -1,16 +1,49 @@
 use anyhow::format_err;
-use chrono::{offset::Local, DateTime, NaiveDate};
+use chrono::{offset::Local, DateTime, Duration as ChronoDuration, NaiveDate};
 use clap::{Parser, Subcommand, ValueEnum};
-use futures::StreamExt;
+use futures::{future::select_all, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::{
+    future::Future,
     io,
     path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 use timeflippers::{
-    timeflip::{Entry, Event, TimeFlip},
+    timeflip::{Entry, Event, SyncState, TimeFlip},
     view, BluetoothSession, Config, Facet,
 };
-use tokio::{fs, select, signal};
+use tokio::{
+    fs, process, select, signal,
+    sync::Mutex,
+    time::{interval, sleep, timeout},
+};
+
+const RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Reconnect to the TimeFlip2, doubling the backoff delay after every failed
+/// attempt up to `RECONNECT_MAX_BACKOFF`.
+async fn reconnect(session: &BluetoothSession, password: Option<String>) -> TimeFlip {
+    let mut backoff = RECONNECT_INITIAL_BACKOFF;
+    loop {
+        log::info!("attempting to reconnect to TimeFlip2");
+        match TimeFlip::connect(session, password.clone()).await {
+            Ok(timeflip) => {
+                log::info!("reconnected");
+                return timeflip;
+            }
+            Err(e) => {
+                log::info!("reconnect failed: {e}, retrying in {backoff:?}");
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+            }
+        }
+    }
+}
 
 async fn read_config(path: impl AsRef<Path>) -> anyhow::Result<Config> {
     let toml = fs::read_to_string(path).await?;
@@ -33,6 +66,19 @@ fn facet_name(facet: &Facet, config: Option<&Config>) -> String {
 struct Options {
     #[arg(short, long, help = "path to the timeflip.toml file")]
     config: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        help = "automatically reconnect with exponential backoff on disconnect"
+    )]
+    reconnect: bool,
+    #[arg(
+        long,
+        global = true,
+        default_value = "text",
+        help = "choose how output is rendered"
+    )]
+    output: OutputFormat,
     #[command(subcommand)]
     cmd: Command,
 }
@@ -44,10 +90,57 @@ enum HistoryStyle {
     Summarized,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum DaemonAction {
+    /// Run the event-log, periodic-sync, and battery-poll workers under one supervisor.
+    Run {
+        #[arg(long, help = "file the event-log worker appends events to")]
+        update: Option<PathBuf>,
+        #[arg(
+            long,
+            default_value_t = 300,
+            help = "interval in seconds between sync_state() checks"
+        )]
+        sync_interval: u64,
+        #[arg(
+            long,
+            default_value_t = 180,
+            help = "interval in seconds between battery polls"
+        )]
+        battery_interval: u64,
+    },
+    /// Run every worker once and print its resulting state.
+    Status,
+}
+
 #[derive(Subcommand)]
 enum Command {
     /// Print the current battery level.
     Battery,
+    /// Periodically check for and merge new entries into an `--update` history
+    /// file, without requiring cron.
+    Autosync {
+        #[arg(long, help = "history file to merge new entries into")]
+        update: PathBuf,
+        #[arg(
+            long,
+            default_value_t = 300,
+            help = "interval in seconds between sync_state() checks"
+        )]
+        watch: u64,
+        #[arg(
+            long,
+            default_value_t = 0,
+            help = "add up to this many random seconds to each interval to avoid thundering-herd polling"
+        )]
+        jitter: u64,
+    },
     /// Print logged TimeFlip events.
     History {
         #[arg(long, help = "read events from and write new events to file")]
@@ -62,12 +155,35 @@ enum Command {
         #[arg(long, help = "choose output style", default_value = "tabular")]
         style: HistoryStyle,
     },
+    /// Run several background workers (event log, periodic sync, battery poll) under one supervisor.
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonAction,
+    },
     /// Print the facet currently facing up.
     Facet,
     /// Put the TimeFlip2 in lock mode.
     Lock,
     /// Release the TimeFlip2 from lock mode.
     Unlock,
+    /// Watch the battery level and report charging state and threshold crossings.
+    Monitor {
+        #[arg(long, default_value_t = 180, help = "poll interval in seconds")]
+        interval: u64,
+        #[arg(long, default_value_t = 15, help = "low battery threshold in percent")]
+        low: u8,
+        #[arg(
+            long,
+            default_value_t = 5,
+            help = "critical battery threshold in percent"
+        )]
+        critical: u8,
+        #[arg(
+            long,
+            help = "command to run on a level or state change, level and state are passed via TIMEFLIP_BATTERY_LEVEL and TIMEFLIP_BATTERY_STATE"
+        )]
+        exec: Option<String>,
+    },
     /// Subscribe to properties and get notified if they change.
     Notify {
         #[arg(long, help = "listen for battery events")]
@@ -78,6 +194,21 @@ enum Command {
         double_tap: bool,
         #[arg(long, help = "listen for log events")]
         log_event: bool,
+        #[arg(
+            long,
+            help = "automatically reconnect with exponential backoff on disconnect"
+        )]
+        reconnect: bool,
+        #[arg(
+            long,
+            help = "buffer battery events for this many milliseconds and emit only the latest one; log events are never buffered"
+        )]
+        debounce: Option<u64>,
+        #[arg(
+            long,
+            help = "with --debounce, also coalesce rapid Facet/DoubleTap transitions instead of emitting every one"
+        )]
+        coalesce_facets: bool,
     },
     /// Put the TimeFlip2 into pause mode.
     Pause,
@@ -98,58 +229,694 @@ enum Command {
     WriteConfig,
 }
 
-impl Command {
-    async fn run(&self, timeflip: &mut TimeFlip, config: Option<Config>) -> anyhow::Result<()> {
-        use Command::*;
-        match self {
-            Battery => {
-                println!("Battery level: {}", timeflip.battery_level().await?);
+/// Re-apply the `subscribe_*` registrations selected by `Notify`'s flags,
+/// e.g. after a reconnect.
+async fn subscribe(
+    timeflip: &mut TimeFlip,
+    battery: bool,
+    facet: bool,
+    double_tap: bool,
+    log_event: bool,
+) -> anyhow::Result<()> {
+    if battery {
+        timeflip.subscribe_battery_level().await?;
+    }
+    if facet {
+        timeflip.subscribe_facet().await?;
+    }
+    if double_tap {
+        timeflip.subscribe_double_tap().await?;
+    }
+    if log_event {
+        timeflip.subscribe_events().await?;
+    }
+    Ok(())
+}
+
+/// Direction the battery level last moved in. A sample equal to the previous
+/// one keeps the previous direction; `Steady` is reported until the level
+/// has changed at least once.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum ChargeState {
+    Charging,
+    Discharging,
+    Steady,
+}
+
+impl std::fmt::Display for ChargeState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ChargeState::Charging => "charging",
+            ChargeState::Discharging => "discharging",
+            ChargeState::Steady => "steady",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Which side of the low/critical thresholds the battery level is on, with a
+/// two-point hysteresis band so a level hovering right at a boundary doesn't
+/// flip back and forth.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum BatteryTier {
+    Normal,
+    Low,
+    Critical,
+}
+
+fn next_tier(tier: BatteryTier, level: u8, low: u8, critical: u8) -> BatteryTier {
+    use BatteryTier::*;
+    match tier {
+        Critical if level > critical.saturating_add(2) => Low,
+        Critical => Critical,
+        Low if level <= critical => Critical,
+        Low if level > low.saturating_add(2) => Normal,
+        Low => Low,
+        Normal if level <= low => Low,
+        Normal => Normal,
+    }
+}
+
+/// Run an external command on a battery level or state change, passing the
+/// new level and state through the environment.
+async fn run_exec(exec: &str, level: u8, state: ChargeState, tier: BatteryTier) {
+    let tier = match tier {
+        BatteryTier::Normal => "normal",
+        BatteryTier::Low => "low",
+        BatteryTier::Critical => "critical",
+    };
+    let status = process::Command::new("sh")
+        .arg("-c")
+        .arg(exec)
+        .env("TIMEFLIP_BATTERY_LEVEL", level.to_string())
+        .env("TIMEFLIP_BATTERY_STATE", state.to_string())
+        .env("TIMEFLIP_BATTERY_TIER", tier)
+        .status()
+        .await;
+    if let Err(e) = status {
+        log::error!("failed to run --exec command {exec:?}: {e}");
+    }
+}
+
+/// The tier a freshly observed level falls into, with no prior reading to
+/// carry hysteresis from.
+fn initial_tier(level: u8, low: u8, critical: u8) -> BatteryTier {
+    if level <= critical {
+        BatteryTier::Critical
+    } else if level <= low {
+        BatteryTier::Low
+    } else {
+        BatteryTier::Normal
+    }
+}
+
+/// The outcome of waiting for the next battery sample.
+enum Sample {
+    Level(u8),
+    Disconnected,
+    Skip,
+}
+
+/// What a long-running command needs to re-establish a dropped connection,
+/// grouped to keep functions like `monitor` from growing a parameter per
+/// reconnect-related flag.
+struct ReconnectContext<'a> {
+    session: &'a BluetoothSession,
+    password: Option<String>,
+    enabled: bool,
+}
+
+/// Poll the battery level, derive the charging state from successive
+/// samples, and report only on changes that matter (level, charge direction,
+/// or threshold crossings).
+async fn monitor(
+    timeflip: &mut TimeFlip,
+    reconnect_ctx: ReconnectContext<'_>,
+    poll_interval: Duration,
+    low: u8,
+    critical: u8,
+    exec: Option<&str>,
+) -> anyhow::Result<()> {
+    let mut prev_level: Option<u8> = None;
+    let mut prev_state = ChargeState::Steady;
+    let mut prev_tier: Option<BatteryTier> = None;
+
+    let use_events = timeflip.subscribe_battery_level().await.is_ok();
+    let mut stream = if use_events {
+        Some(timeflip.event_stream().await?)
+    } else {
+        None
+    };
+    let mut ticker = interval(poll_interval);
+
+    loop {
+        let sample = match stream.as_mut() {
+            Some(stream) => select! {
+                event = stream.next() => match event {
+                    Some(Event::BatteryLevel(level)) => Sample::Level(level),
+                    Some(Event::Disconnected) | None => Sample::Disconnected,
+                    Some(_) => Sample::Skip,
+                },
+                _ = ticker.tick() => Sample::Level(timeflip.battery_level().await?),
+            },
+            None => {
+                ticker.tick().await;
+                Sample::Level(timeflip.battery_level().await?)
             }
-            History {
-                update: update_file,
-                start_with,
-                style,
-                since,
-            } => {
-                let config = config.ok_or(format_err!("config is mandatory for this command"))?;
+        };
+
+        let level = match sample {
+            Sample::Level(level) => level,
+            Sample::Skip => continue,
+            Sample::Disconnected if reconnect_ctx.enabled => {
+                println!("TimeFlip has disconnected, reconnecting...");
+                *timeflip = reconnect(reconnect_ctx.session, reconnect_ctx.password.clone()).await;
+                stream = if timeflip.subscribe_battery_level().await.is_ok() {
+                    Some(timeflip.event_stream().await?)
+                } else {
+                    None
+                };
+                continue;
+            }
+            Sample::Disconnected => {
+                println!("TimeFlip has disconnected");
+                break;
+            }
+        };
+
+        let state = match prev_level {
+            Some(prev) if level > prev => ChargeState::Charging,
+            Some(prev) if level < prev => ChargeState::Discharging,
+            Some(_) => prev_state,
+            None => ChargeState::Steady,
+        };
+        let tier = match prev_tier {
+            Some(prev) => next_tier(prev, level, low, critical),
+            None => initial_tier(level, low, critical),
+        };
+
+        let changed = prev_level != Some(level) || state != prev_state || prev_tier != Some(tier);
+        if changed {
+            println!("Battery level {level}% ({state}, {tier:?})");
+            if let Some(exec) = exec {
+                run_exec(exec, level, state, tier).await;
+            }
+        }
+
+        prev_level = Some(level);
+        prev_state = state;
+        prev_tier = Some(tier);
+    }
+
+    Ok(())
+}
+
+/// The result of one `Worker::step`.
+#[derive(Clone, Debug)]
+enum WorkerState {
+    Active,
+    Idle { next_run: DateTime<Local> },
+    Dead { error: String },
+}
 
-                let (start_with, mut entries) = if let Some(file) = update_file {
-                    match fs::read_to_string(file).await {
-                        Ok(s) => {
-                            let mut entries: Vec<Entry> = serde_json::from_str(&s)?;
-                            entries.sort_by(|a, b| a.id.cmp(&b.id));
-                            (
-                                start_with
-                                    .or_else(|| entries.last().map(|e| e.id))
-                                    .unwrap_or(0),
-                                entries,
-                            )
+/// A background job driven one step at a time by the `Daemon` supervisor.
+///
+/// `step` takes the worker by value and hands it back alongside the
+/// resulting state, so the returned future owns everything it touches
+/// instead of borrowing from the supervisor. That lets `WorkerManager` keep
+/// every worker's in-flight step alive across loop iterations and only
+/// re-issue the one that just completed, rather than cancelling the rest.
+trait Worker {
+    fn name(&self) -> &str;
+
+    /// Make progress and report the resulting state. Implementations are
+    /// expected to recover on their own (e.g. resubscribing) rather than
+    /// staying `Dead` forever.
+    fn step(self: Box<Self>) -> Pin<Box<dyn Future<Output = (Box<dyn Worker>, WorkerState)>>>;
+}
+
+/// Persists every event from `event_stream()` to the `--update` JSON file,
+/// same subscriptions as `Notify --battery --facet --double-tap --log-event`.
+struct EventLogWorker {
+    timeflip: Arc<Mutex<TimeFlip>>,
+    update_file: Option<PathBuf>,
+    stream: Option<Pin<Box<dyn Stream<Item = Event>>>>,
+}
+
+async fn append_json_event(file: &Path, event: serde_json::Value) -> anyhow::Result<()> {
+    let mut events: Vec<serde_json::Value> = match fs::read_to_string(file).await {
+        Ok(s) => serde_json::from_str(&s)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e.into()),
+    };
+    events.push(event);
+    fs::write(file, serde_json::to_vec(&events)?).await?;
+    Ok(())
+}
+
+impl Worker for EventLogWorker {
+    fn name(&self) -> &str {
+        "event-log"
+    }
+
+    fn step(mut self: Box<Self>) -> Pin<Box<dyn Future<Output = (Box<dyn Worker>, WorkerState)>>> {
+        Box::pin(async move {
+            if self.stream.is_none() {
+                let mut timeflip = self.timeflip.lock().await;
+                if let Err(e) = subscribe(&mut timeflip, true, true, true, true).await {
+                    return (self as Box<dyn Worker>, WorkerState::Dead { error: e.to_string() });
+                }
+                match timeflip.event_stream().await {
+                    Ok(stream) => self.stream = Some(Box::pin(stream)),
+                    Err(e) => {
+                        return (self as Box<dyn Worker>, WorkerState::Dead { error: e.to_string() })
+                    }
+                }
+            }
+
+            let event = self.stream.as_mut().expect("just initialized").next().await;
+            let state = match event {
+                Some(Event::Disconnected) => {
+                    self.stream = None;
+                    WorkerState::Dead {
+                        error: "TimeFlip has disconnected".into(),
+                    }
+                }
+                Some(event) => {
+                    if let Some(file) = &self.update_file {
+                        let value = match &event {
+                            Event::BatteryLevel(percent) => {
+                                json!({ "event": "battery_level", "percent": percent })
+                            }
+                            Event::Event(log_event) => {
+                                json!({ "event": "log_event", "detail": log_event.to_string() })
+                            }
+                            Event::Facet(facet) => {
+                                json!({ "event": "facet", "facet": facet.to_string() })
+                            }
+                            Event::DoubleTap { facet, pause } => {
+                                json!({ "event": "double_tap", "facet": facet.to_string(), "pause": pause })
+                            }
+                            Event::Disconnected => unreachable!("handled above"),
+                        };
+                        if let Err(e) = append_json_event(file, value).await {
+                            log::error!("cannot append event to {}: {e}", file.display());
                         }
-                        Err(e) if e.kind() == io::ErrorKind::NotFound => {
-                            (start_with.unwrap_or(0), vec![])
+                    }
+                    WorkerState::Active
+                }
+                None => WorkerState::Dead {
+                    error: "event stream closed".into(),
+                },
+            };
+            (self as Box<dyn Worker>, state)
+        })
+    }
+}
+
+/// Calls `timeflip.sync()` whenever `sync_state()` reports unsynchronized
+/// entries, otherwise idles until the next check.
+struct PeriodicSyncWorker {
+    timeflip: Arc<Mutex<TimeFlip>>,
+    config: Config,
+    check_interval: Duration,
+}
+
+impl Worker for PeriodicSyncWorker {
+    fn name(&self) -> &str {
+        "periodic-sync"
+    }
+
+    fn step(self: Box<Self>) -> Pin<Box<dyn Future<Output = (Box<dyn Worker>, WorkerState)>>> {
+        Box::pin(async move {
+            let sync_state = self.timeflip.lock().await.sync_state().await;
+            let state = match sync_state {
+                Ok(SyncState::Synchronized) => {
+                    let next_run = Local::now()
+                        + ChronoDuration::from_std(self.check_interval)
+                            .unwrap_or(ChronoDuration::seconds(60));
+                    // Actually idle for the interval, rather than merely reporting one,
+                    // so this worker doesn't spin back around immediately and starve
+                    // the others out of the connection.
+                    sleep(self.check_interval).await;
+                    WorkerState::Idle { next_run }
+                }
+                Ok(_unsynchronized) => match self.timeflip.lock().await.sync(&self.config).await {
+                    Ok(()) => WorkerState::Active,
+                    Err(e) => WorkerState::Dead { error: e.to_string() },
+                },
+                Err(e) => WorkerState::Dead { error: e.to_string() },
+            };
+            (self as Box<dyn Worker>, state)
+        })
+    }
+}
+
+/// Polls the battery level on an interval, idling in between.
+struct BatteryPollWorker {
+    timeflip: Arc<Mutex<TimeFlip>>,
+    poll_interval: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl Worker for BatteryPollWorker {
+    fn name(&self) -> &str {
+        "battery-poll"
+    }
+
+    fn step(mut self: Box<Self>) -> Pin<Box<dyn Future<Output = (Box<dyn Worker>, WorkerState)>>> {
+        Box::pin(async move {
+            if let Some(last) = self.last_poll {
+                let elapsed = last.elapsed();
+                if elapsed < self.poll_interval {
+                    sleep(self.poll_interval - elapsed).await;
+                }
+            }
+            self.last_poll = Some(Instant::now());
+            let state = match self.timeflip.lock().await.battery_level().await {
+                Ok(level) => {
+                    log::info!("battery level: {level}%");
+                    WorkerState::Active
+                }
+                Err(e) => WorkerState::Dead { error: e.to_string() },
+            };
+            (self as Box<dyn Worker>, state)
+        })
+    }
+}
+
+/// A worker's step in flight, owning the worker it was created from.
+type WorkerStep = Pin<Box<dyn Future<Output = (Box<dyn Worker>, WorkerState)>>>;
+
+/// Drives a fixed set of `Worker`s to completion-or-forever, restarting
+/// their own recovery logic on each step and logging the state each step
+/// ends in.
+///
+/// Names are captured up front (rather than read from `workers` later)
+/// because steps in flight own their worker, so there's no `Box<dyn Worker>`
+/// left in `self` to ask for a name while a step is running.
+struct WorkerManager {
+    names: Vec<String>,
+    states: Vec<WorkerState>,
+    steps: Vec<WorkerStep>,
+}
+
+impl WorkerManager {
+    fn new(workers: Vec<Box<dyn Worker>>) -> Self {
+        let names = workers.iter().map(|w| w.name().to_string()).collect();
+        let states = workers.iter().map(|_| WorkerState::Active).collect();
+        let steps = workers.into_iter().map(|w| w.step()).collect();
+        Self {
+            names,
+            states,
+            steps,
+        }
+    }
+
+    fn status_lines(&self) -> Vec<String> {
+        self.names
+            .iter()
+            .zip(&self.states)
+            .map(|(name, state)| format!("{name}: {state:?}"))
+            .collect()
+    }
+
+    /// Run every worker once, bounded by `per_step_timeout` so a cube that never
+    /// answers (or a worker idling out its own interval) can't hang a
+    /// one-shot `daemon status` snapshot forever. This inspects a fresh set
+    /// of workers connected just for the snapshot, not the state of an
+    /// actual running `daemon run` process.
+    async fn step_once(&mut self, per_step_timeout: Duration) {
+        let steps = std::mem::take(&mut self.steps);
+        for (index, step) in steps.into_iter().enumerate() {
+            self.states[index] = match timeout(per_step_timeout, step).await {
+                Ok((_worker, state)) => state,
+                Err(_) => WorkerState::Dead {
+                    error: format!("worker did not respond within {per_step_timeout:?}"),
+                },
+            };
+        }
+    }
+
+    async fn run(mut self) {
+        loop {
+            let steps = std::mem::take(&mut self.steps);
+            select! {
+                _ = signal::ctrl_c() => {
+                    log::info!("daemon shutting down");
+                    break;
+                }
+                ((worker, state), index, remaining) = select_all(steps) => {
+                    match &state {
+                        WorkerState::Active => log::info!("worker {} active", self.names[index]),
+                        WorkerState::Idle { next_run } => {
+                            log::debug!("worker {} idle until {next_run}", self.names[index])
+                        }
+                        WorkerState::Dead { error } => {
+                            log::error!("worker {} reported an error: {error}", self.names[index])
                         }
-                        Err(e) => return Err(e.into()),
                     }
-                } else {
-                    (start_with.unwrap_or(0), vec![])
-                };
+                    self.states[index] = state;
 
-                let mut update = timeflip.read_history_since(start_with).await?;
+                    // Only the worker that just completed gets a new step;
+                    // every other worker's in-flight step is kept as-is
+                    // instead of being cancelled and restarted from scratch.
+                    // `select_all` swap-removes the completed future, so the
+                    // new step is pushed and swapped back into `index` to keep
+                    // `steps` aligned with `names` and `states`.
+                    let mut steps = remaining;
+                    steps.push(worker.step());
+                    let last = steps.len() - 1;
+                    steps.swap(index, last);
+                    self.steps = steps;
+                }
+            }
+        }
+    }
+}
 
-                let new_ids = update.iter().map(|e| e.id).collect::<Vec<_>>();
-                entries.retain(|entry| !new_ids.contains(&entry.id));
-                entries.append(&mut update);
+/// Print a single `Notify` event in the chosen `OutputFormat`. `Event::Disconnected`
+/// is handled by the caller, since reconnect/break decisions live there.
+fn print_event(event: &Event, config: Option<&Config>, output: OutputFormat) {
+    match event {
+        Event::BatteryLevel(percent) => match output {
+            OutputFormat::Text => println!("Battery Level {percent}"),
+            OutputFormat::Json => println!("{}", json!({ "event": "battery_level", "percent": percent })),
+        },
+        Event::Event(log_event) => match output {
+            OutputFormat::Text => println!("{log_event}"),
+            OutputFormat::Json => {
+                println!("{}", json!({ "event": "log_event", "detail": log_event.to_string() }))
+            }
+        },
+        Event::Facet(facet) => {
+            let name = facet_name(facet, config);
+            match output {
+                OutputFormat::Text => println!("Currently Up: {name}"),
+                OutputFormat::Json => println!("{}", json!({ "event": "facet", "facet": name })),
+            }
+        }
+        Event::DoubleTap { facet, pause } => {
+            let name = facet_name(facet, config);
+            match output {
+                OutputFormat::Text => println!(
+                    "Facet {name} has {}",
+                    if *pause { "paused" } else { "started" }
+                ),
+                OutputFormat::Json => {
+                    println!("{}", json!({ "event": "double_tap", "facet": name, "pause": pause }))
+                }
+            }
+        }
+        Event::Disconnected => {}
+    }
+}
+
+/// Emit and clear whatever `Notify`'s debounce window has buffered, in a
+/// stable battery/facet/double-tap order.
+fn flush_pending(
+    battery: &mut Option<u8>,
+    facet: &mut Option<Facet>,
+    double_tap: &mut Option<(Facet, bool)>,
+    config: Option<&Config>,
+    output: OutputFormat,
+) {
+    if let Some(percent) = battery.take() {
+        print_event(&Event::BatteryLevel(percent), config, output);
+    }
+    if let Some(facet) = facet.take() {
+        print_event(&Event::Facet(facet), config, output);
+    }
+    if let Some((facet, pause)) = double_tap.take() {
+        print_event(&Event::DoubleTap { facet, pause }, config, output);
+    }
+}
+
+/// Read `update_file` (if any), fetch everything newer than `start_with` (or
+/// the file's latest entry), merge and de-duplicate by ID, write the result
+/// back out, and return it along with how many entries were newly fetched.
+async fn merge_history(
+    timeflip: &mut TimeFlip,
+    update_file: Option<&Path>,
+    start_with: Option<u32>,
+) -> anyhow::Result<(Vec<Entry>, usize)> {
+    let (start_with, mut entries) = if let Some(file) = update_file {
+        match fs::read_to_string(file).await {
+            Ok(s) => {
+                let mut entries: Vec<Entry> = serde_json::from_str(&s)?;
+                entries.sort_by(|a, b| a.id.cmp(&b.id));
+                (
+                    start_with
+                        .or_else(|| entries.last().map(|e| e.id))
+                        .unwrap_or(0),
+                    entries,
+                )
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (start_with.unwrap_or(0), vec![]),
+            Err(e) => return Err(e.into()),
+        }
+    } else {
+        (start_with.unwrap_or(0), vec![])
+    };
+
+    let mut update = timeflip.read_history_since(start_with).await?;
+    let new_count = update.len();
 
-                if let Some(file) = update_file {
-                    match serde_json::to_vec(&entries) {
+    let new_ids = update.iter().map(|e| e.id).collect::<Vec<_>>();
+    entries.retain(|entry| !new_ids.contains(&entry.id));
+    entries.append(&mut update);
+
+    if let Some(file) = update_file {
+        match serde_json::to_vec(&entries) {
+            Ok(json) => {
+                if let Err(e) = fs::write(file, json).await {
+                    eprintln!("cannot update entries file {}: {e}", file.display());
+                }
+            }
+            Err(e) => eprintln!("cannot update entries file {}: {e}", file.display()),
+        }
+    }
+
+    Ok((entries, new_count))
+}
+
+/// Small persisted bookmark so `Autosync` can resume after a restart without
+/// re-deriving its position from the (potentially large) history file.
+#[derive(Serialize, Deserialize)]
+struct SyncCursor {
+    last_id: u32,
+    last_synced_at: DateTime<Local>,
+}
+
+/// Wake up on `watch` (plus up to `jitter`), sync if needed, and merge any
+/// newly available entries into `update_file`, forever.
+async fn autosync(
+    timeflip: &mut TimeFlip,
+    config: &Config,
+    update_file: &Path,
+    watch: Duration,
+    jitter: Duration,
+) -> anyhow::Result<()> {
+    let cursor_file = PathBuf::from(format!("{}.cursor", update_file.display()));
+
+    loop {
+        let cursor: Option<SyncCursor> = match fs::read_to_string(&cursor_file).await {
+            Ok(s) => serde_json::from_str(&s).ok(),
+            Err(_) => None,
+        };
+
+        match timeflip.sync_state().await {
+            Ok(SyncState::Synchronized) => {}
+            Ok(_unsynchronized) => {
+                if let Err(e) = timeflip.sync(config).await {
+                    log::error!("autosync: sync failed: {e}");
+                }
+            }
+            Err(e) => log::error!("autosync: cannot read sync state: {e}"),
+        }
+
+        match merge_history(timeflip, Some(update_file), cursor.map(|c| c.last_id)).await {
+            Ok((entries, new_count)) => {
+                log::info!("autosync: merged {new_count} new entries");
+
+                if let Some(last_id) = entries.last().map(|e| e.id) {
+                    let cursor = SyncCursor {
+                        last_id,
+                        last_synced_at: Local::now(),
+                    };
+                    match serde_json::to_vec(&cursor) {
                         Ok(json) => {
-                            if let Err(e) = fs::write(file, json).await {
-                                eprintln!("cannot update entries file {}: {e}", file.display());
+                            if let Err(e) = fs::write(&cursor_file, json).await {
+                                log::error!(
+                                    "autosync: cannot persist cursor to {}: {e}",
+                                    cursor_file.display()
+                                );
                             }
                         }
-                        Err(e) => eprintln!("cannot update entries file {}: {e}", file.display()),
+                        Err(e) => log::error!("autosync: cannot serialize cursor: {e}"),
                     }
                 }
+            }
+            Err(e) => log::error!("autosync: cannot merge history: {e}"),
+        }
+
+        let jitter_secs = if jitter.is_zero() {
+            0
+        } else {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .subsec_nanos() as u64;
+            nanos % (jitter.as_secs() + 1)
+        };
+        sleep(watch + Duration::from_secs(jitter_secs)).await;
+    }
+}
+
+impl Command {
+    async fn run(
+        &self,
+        timeflip: &mut TimeFlip,
+        config: Option<Config>,
+        session: &BluetoothSession,
+        password: Option<String>,
+        global_reconnect: bool,
+        output: OutputFormat,
+    ) -> anyhow::Result<()> {
+        use Command::*;
+        match self {
+            Battery => {
+                let level = timeflip.battery_level().await?;
+                match output {
+                    OutputFormat::Text => println!("Battery level: {level}"),
+                    OutputFormat::Json => println!("{}", json!({ "level": level })),
+                }
+            }
+            Autosync {
+                update,
+                watch,
+                jitter,
+            } => {
+                let config = config.ok_or(format_err!("config is mandatory for this command"))?;
+                autosync(
+                    timeflip,
+                    &config,
+                    update,
+                    Duration::from_secs(*watch),
+                    Duration::from_secs(*jitter),
+                )
+                .await?;
+            }
+            History {
+                update: update_file,
+                start_with,
+                style,
+                since,
+            } => {
+                let config = config.ok_or(format_err!("config is mandatory for this command"))?;
+
+                let (entries, _new_count) =
+                    merge_history(timeflip, update_file.as_deref(), *start_with).await?;
 
                 let history = view::History::new(entries, config);
                 let filtered = if let Some(since) = since {
@@ -163,65 +930,237 @@ impl Command {
                     history.all()
                 };
                 use HistoryStyle::*;
-                match style {
-                    Lines => println!("{}", filtered),
-                    Tabular => println!("{}", filtered.table_by_day()),
-                    Summarized => println!("{}", filtered.summarized()),
+                match (output, style) {
+                    (OutputFormat::Json, Lines) => println!("{}", serde_json::to_string(&filtered)?),
+                    (OutputFormat::Json, Tabular) => {
+                        println!("{}", serde_json::to_string(&filtered.table_by_day())?)
+                    }
+                    (OutputFormat::Json, Summarized) => {
+                        println!("{}", serde_json::to_string(&filtered.summarized())?)
+                    }
+                    (OutputFormat::Text, Lines) => println!("{}", filtered),
+                    (OutputFormat::Text, Tabular) => println!("{}", filtered.table_by_day()),
+                    (OutputFormat::Text, Summarized) => println!("{}", filtered.summarized()),
+                }
+            }
+            Daemon { action } => {
+                let config = config.ok_or(format_err!("config is mandatory for this command"))?;
+                // The daemon supervises workers for the lifetime of the process, so it
+                // keeps its own connection rather than borrowing the one `main` opened.
+                let owned = TimeFlip::connect(session, password.clone()).await?;
+                let shared = Arc::new(Mutex::new(owned));
+
+                match action {
+                    DaemonAction::Run {
+                        update,
+                        sync_interval,
+                        battery_interval,
+                    } => {
+                        let workers: Vec<Box<dyn Worker>> = vec![
+                            Box::new(EventLogWorker {
+                                timeflip: shared.clone(),
+                                update_file: update.clone(),
+                                stream: None,
+                            }),
+                            Box::new(PeriodicSyncWorker {
+                                timeflip: shared.clone(),
+                                config,
+                                check_interval: Duration::from_secs(*sync_interval),
+                            }),
+                            Box::new(BatteryPollWorker {
+                                timeflip: shared.clone(),
+                                poll_interval: Duration::from_secs(*battery_interval),
+                                last_poll: None,
+                            }),
+                        ];
+                        WorkerManager::new(workers).run().await;
+                    }
+                    DaemonAction::Status => {
+                        let workers: Vec<Box<dyn Worker>> = vec![
+                            Box::new(EventLogWorker {
+                                timeflip: shared.clone(),
+                                update_file: None,
+                                stream: None,
+                            }),
+                            Box::new(PeriodicSyncWorker {
+                                timeflip: shared.clone(),
+                                config,
+                                check_interval: Duration::from_secs(300),
+                            }),
+                            Box::new(BatteryPollWorker {
+                                timeflip: shared.clone(),
+                                poll_interval: Duration::from_secs(180),
+                                last_poll: None,
+                            }),
+                        ];
+                        let mut manager = WorkerManager::new(workers);
+                        manager.step_once(Duration::from_secs(5)).await;
+                        for line in manager.status_lines() {
+                            println!("{line}");
+                        }
+                    }
                 }
             }
             Facet => {
                 let facet = timeflip.facet().await?;
-                println!("Currently up: {}", facet_name(&facet, config.as_ref()));
+                let name = facet_name(&facet, config.as_ref());
+                match output {
+                    OutputFormat::Text => println!("Currently up: {name}"),
+                    OutputFormat::Json => println!("{}", json!({ "facet": name })),
+                }
             }
             Lock => timeflip.lock().await?,
             Unlock => timeflip.unlock().await?,
+            Monitor {
+                interval,
+                low,
+                critical,
+                exec,
+            } => {
+                monitor(
+                    timeflip,
+                    ReconnectContext {
+                        session,
+                        password: password.clone(),
+                        enabled: global_reconnect,
+                    },
+                    Duration::from_secs(*interval),
+                    *low,
+                    *critical,
+                    exec.as_deref(),
+                )
+                .await?;
+            }
             Notify {
                 battery,
                 facet,
                 double_tap,
                 log_event,
+                reconnect: reconnect_flag,
+                debounce,
+                coalesce_facets,
             } => {
-                if *battery {
-                    timeflip.subscribe_battery_level().await?;
-                }
-                if *facet {
-                    timeflip.subscribe_facet().await?;
-                }
-                if *double_tap {
-                    timeflip.subscribe_double_tap().await?;
-                }
-                if *log_event {
-                    timeflip.subscribe_events().await?;
-                }
+                let reconnect_on_disconnect = *reconnect_flag || global_reconnect;
+                let window = debounce.map(Duration::from_millis);
 
+                subscribe(timeflip, *battery, *facet, *double_tap, *log_event).await?;
                 let mut stream = timeflip.event_stream().await?;
+
+                let mut pending_battery: Option<u8> = None;
+                let mut pending_facet: Option<timeflippers::Facet> = None;
+                let mut pending_double_tap: Option<(timeflippers::Facet, bool)> = None;
+                let mut deadline: Option<Instant> = None;
+
                 loop {
-                    match stream.next().await {
-                        Some(Event::BatteryLevel(percent)) => println!("Battery Level {percent}"),
-                        Some(Event::Event(event)) => println!("{event}"),
-                        Some(Event::Facet(facet)) => {
-                            println!("Currently Up: {}", facet_name(&facet, config.as_ref()))
+                    let wait_for_deadline = async {
+                        match deadline {
+                            Some(at) => {
+                                let now = Instant::now();
+                                if at > now {
+                                    sleep(at - now).await;
+                                }
+                            }
+                            None => std::future::pending::<()>().await,
                         }
-                        Some(Event::DoubleTap { facet, pause }) => println!(
-                            "Facet {} has {}",
-                            facet_name(&facet, config.as_ref()),
-                            if pause { "paused" } else { "started" }
-                        ),
-                        Some(Event::Disconnected) => {
-                            println!("TimeFlip has disconnected");
-                            break;
+                    };
+
+                    select! {
+                        event = stream.next() => match event {
+                            Some(Event::Disconnected) => {
+                                flush_pending(
+                                    &mut pending_battery,
+                                    &mut pending_facet,
+                                    &mut pending_double_tap,
+                                    config.as_ref(),
+                                    output,
+                                );
+                                deadline = None;
+                                if reconnect_on_disconnect {
+                                    match output {
+                                        OutputFormat::Text => println!("TimeFlip has disconnected, reconnecting..."),
+                                        OutputFormat::Json => println!(
+                                            "{}",
+                                            json!({ "event": "disconnected", "reconnecting": true })
+                                        ),
+                                    }
+                                    *timeflip = reconnect(session, password.clone()).await;
+                                    subscribe(timeflip, *battery, *facet, *double_tap, *log_event).await?;
+                                    stream = timeflip.event_stream().await?;
+                                } else {
+                                    match output {
+                                        OutputFormat::Text => println!("TimeFlip has disconnected"),
+                                        OutputFormat::Json => println!(
+                                            "{}",
+                                            json!({ "event": "disconnected", "reconnecting": false })
+                                        ),
+                                    }
+                                    break;
+                                }
+                            }
+                            Some(Event::BatteryLevel(percent)) => {
+                                match window {
+                                    Some(window) => {
+                                        pending_battery = Some(percent);
+                                        deadline.get_or_insert(Instant::now() + window);
+                                    }
+                                    None => print_event(&Event::BatteryLevel(percent), config.as_ref(), output),
+                                }
+                            }
+                            Some(Event::Facet(facet)) => {
+                                match window {
+                                    Some(window) if *coalesce_facets => {
+                                        pending_facet = Some(facet);
+                                        deadline.get_or_insert(Instant::now() + window);
+                                    }
+                                    _ => print_event(&Event::Facet(facet), config.as_ref(), output),
+                                }
+                            }
+                            Some(Event::DoubleTap { facet, pause }) => {
+                                match window {
+                                    Some(window) if *coalesce_facets => {
+                                        pending_double_tap = Some((facet, pause));
+                                        deadline.get_or_insert(Instant::now() + window);
+                                    }
+                                    _ => print_event(
+                                        &Event::DoubleTap { facet, pause },
+                                        config.as_ref(),
+                                        output,
+                                    ),
+                                }
+                            }
+                            Some(event @ Event::Event(_)) => print_event(&event, config.as_ref(), output),
+                            None => break,
+                        },
+                        _ = wait_for_deadline => {
+                            flush_pending(
+                                &mut pending_battery,
+                                &mut pending_facet,
+                                &mut pending_double_tap,
+                                config.as_ref(),
+                                output,
+                            );
+                            deadline = None;
                         }
-                        None => break,
                     }
                 }
             }
             Pause => timeflip.pause().await?,
             Unpause => timeflip.unpause().await?,
             Status => {
-                println!("System status: {:?}", timeflip.system_status().await?);
+                let status = timeflip.system_status().await?;
+                match output {
+                    OutputFormat::Text => println!("System status: {status:?}"),
+                    OutputFormat::Json => println!("{}", json!({ "status": format!("{status:?}") })),
+                }
             }
             SyncState => {
-                println!("Sync state: {:?}", timeflip.sync_state().await?);
+                let state = timeflip.sync_state().await?;
+                match output {
+                    OutputFormat::Text => println!("Sync state: {state:?}"),
+                    OutputFormat::Json => {
+                        println!("{}", json!({ "sync_state": format!("{state:?}") }))
+                    }
+                }
             }
             Sync => {
                 let config = config.ok_or(format_err!("config is mandatory for this command"))?;
@@ -230,12 +1169,18 @@ impl Command {
             Time { set } => {
                 if *set {
                     let now = Local::now();
-                    println!("Setting time to: {now}");
                     timeflip.set_time(now.into()).await?;
+                    match output {
+                        OutputFormat::Text => println!("Setting time to: {now}"),
+                        OutputFormat::Json => println!("{}", json!({ "time_set": now.to_rfc3339() })),
+                    }
                 } else {
                     let tz = Local::now().timezone();
-                    let time = timeflip.time().await?;
-                    println!("Time set on TimeFlip: {}", time.with_timezone(&tz));
+                    let time = timeflip.time().await?.with_timezone(&tz);
+                    match output {
+                        OutputFormat::Text => println!("Time set on TimeFlip: {time}"),
+                        OutputFormat::Json => println!("{}", json!({ "time": time.to_rfc3339() })),
+                    }
                 }
             }
             WriteConfig => {
@@ -260,8 +1205,8 @@ async fn main() -> anyhow::Result<()> {
 
     let (mut bg_task, session) = BluetoothSession::new().await?;
 
-    let mut timeflip =
-        TimeFlip::connect(&session, config.as_ref().map(|c| c.password.clone())).await?;
+    let password = config.as_ref().map(|c| c.password.clone());
+    let mut timeflip = TimeFlip::connect(&session, password.clone()).await?;
     log::info!("connected");
 
     select! {
@@ -273,7 +1218,7 @@ async fn main() -> anyhow::Result<()> {
                 log::error!("bluetooth session background task exited with error: {e}");
             }
         }
-        res = opt.cmd.run(&mut timeflip, config) => {
+        res = opt.cmd.run(&mut timeflip, config, &session, password, opt.reconnect, opt.output) => {
             res?;
         }
     }